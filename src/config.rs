@@ -0,0 +1,108 @@
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use libp2p::Multiaddr;
+use serde::Deserialize;
+
+const DEFAULT_RELAY: &str =
+    "/ip4/104.131.131.82/udp/4001/quic-v1/p2p/QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ";
+
+#[derive(Deserialize, Default)]
+struct RawConfig {
+    relays: Option<Vec<String>>,
+    proxy_listen_addr: Option<SocketAddr>,
+    upstream_addr: Option<SocketAddr>,
+}
+
+/// Runtime configuration: the relays to dial, the local proxy listen address, and the
+/// upstream HTTP-proxy bind address. Falls back to the historical hardcoded relay and
+/// ports when no `--config` file is given.
+pub struct Config {
+    pub relays: Vec<Multiaddr>,
+    pub proxy_listen_addr: SocketAddr,
+    pub upstream_addr: SocketAddr,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            relays: vec![DEFAULT_RELAY
+                .parse()
+                .expect("default relay address is valid")],
+            proxy_listen_addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            upstream_addr: SocketAddr::from(([127, 0, 0, 1], 8090)),
+        }
+    }
+}
+
+impl Config {
+    /// Loads config from `path` if given, otherwise returns the defaults. Fields left
+    /// unset in the config file fall back to their default values individually.
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let raw = match path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read config file {}", path))?;
+                toml::from_str(&contents)
+                    .with_context(|| format!("failed to parse config file {}", path))?
+            }
+            None => RawConfig::default(),
+        };
+        let defaults = Config::default();
+
+        let relays = match raw.relays {
+            Some(relays) if !relays.is_empty() => relays
+                .iter()
+                .map(|addr| addr.parse())
+                .collect::<Result<Vec<Multiaddr>, _>>()
+                .context("invalid relay multiaddr in config")?,
+            _ => defaults.relays,
+        };
+
+        Ok(Self {
+            relays,
+            proxy_listen_addr: raw.proxy_listen_addr.unwrap_or(defaults.proxy_listen_addr),
+            upstream_addr: raw.upstream_addr.unwrap_or(defaults.upstream_addr),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_with_no_path_returns_defaults() {
+        let config = Config::load(None).unwrap();
+        let defaults = Config::default();
+        assert_eq!(config.relays, defaults.relays);
+        assert_eq!(config.proxy_listen_addr, defaults.proxy_listen_addr);
+        assert_eq!(config.upstream_addr, defaults.upstream_addr);
+    }
+
+    #[test]
+    fn load_merges_partial_config_with_defaults() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("kadugu-config-test-{}.toml", std::process::id()));
+        std::fs::write(&path, "upstream_addr = \"127.0.0.1:9000\"\n").unwrap();
+
+        let config = Config::load(Some(path.to_str().unwrap())).unwrap();
+        let defaults = Config::default();
+        assert_eq!(config.relays, defaults.relays);
+        assert_eq!(config.proxy_listen_addr, defaults.proxy_listen_addr);
+        assert_eq!(config.upstream_addr, SocketAddr::from(([127, 0, 0, 1], 9000)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_invalid_relay_multiaddr() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("kadugu-config-test-bad-relay-{}.toml", std::process::id()));
+        std::fs::write(&path, "relays = [\"not-a-multiaddr\"]\n").unwrap();
+
+        assert!(Config::load(Some(path.to_str().unwrap())).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
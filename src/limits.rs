@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use libp2p::PeerId;
+use tokio::sync::Mutex;
+
+/// Caps how many `/proxy` streams a single peer may have open concurrently.
+///
+/// Complements the swarm-level `connection_limits::Behaviour`, which only bounds
+/// connections, not streams multiplexed over an already-established connection.
+#[derive(Clone)]
+pub struct StreamLimiter {
+    open: Arc<Mutex<HashMap<PeerId, usize>>>,
+    max_per_peer: Option<usize>,
+}
+
+impl StreamLimiter {
+    pub fn new(max_per_peer: Option<usize>) -> Self {
+        Self {
+            open: Arc::new(Mutex::new(HashMap::new())),
+            max_per_peer,
+        }
+    }
+
+    /// Attempts to reserve a stream slot for `peer`. Returns `false` if the peer is
+    /// already at its limit, in which case the caller should reject the stream.
+    pub async fn try_acquire(&self, peer: &PeerId) -> bool {
+        let Some(max_per_peer) = self.max_per_peer else {
+            return true;
+        };
+        let mut open = self.open.lock().await;
+        let count = open.entry(*peer).or_insert(0);
+        if *count >= max_per_peer {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    /// Releases a previously acquired stream slot for `peer`.
+    pub async fn release(&self, peer: &PeerId) {
+        let mut open = self.open.lock().await;
+        if let Some(count) = open.get_mut(peer) {
+            *count = count.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn no_limit_always_acquires() {
+        let limiter = StreamLimiter::new(None);
+        let peer = PeerId::random();
+        for _ in 0..100 {
+            assert!(limiter.try_acquire(&peer).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_once_at_limit() {
+        let limiter = StreamLimiter::new(Some(2));
+        let peer = PeerId::random();
+        assert!(limiter.try_acquire(&peer).await);
+        assert!(limiter.try_acquire(&peer).await);
+        assert!(!limiter.try_acquire(&peer).await);
+    }
+
+    #[tokio::test]
+    async fn release_frees_a_slot() {
+        let limiter = StreamLimiter::new(Some(1));
+        let peer = PeerId::random();
+        assert!(limiter.try_acquire(&peer).await);
+        assert!(!limiter.try_acquire(&peer).await);
+        limiter.release(&peer).await;
+        assert!(limiter.try_acquire(&peer).await);
+    }
+
+    #[tokio::test]
+    async fn release_below_zero_does_not_panic() {
+        let limiter = StreamLimiter::new(Some(1));
+        let peer = PeerId::random();
+        limiter.release(&peer).await;
+        assert!(limiter.try_acquire(&peer).await);
+    }
+
+    #[tokio::test]
+    async fn limit_is_tracked_per_peer() {
+        let limiter = StreamLimiter::new(Some(1));
+        let a = PeerId::random();
+        let b = PeerId::random();
+        assert!(limiter.try_acquire(&a).await);
+        assert!(limiter.try_acquire(&b).await);
+        assert!(!limiter.try_acquire(&a).await);
+    }
+}
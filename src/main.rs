@@ -1,9 +1,16 @@
+mod config;
+mod limits;
 mod proxyserver;
+mod quota;
+mod relaypool;
+mod swarmkey;
 
-use libp2p::Multiaddr;
+use libp2p::core::upgrade::Version;
+use libp2p::core::Transport;
 use libp2p::{
-    dcutr, identify, identity::Keypair, multiaddr::Protocol, noise, relay, swarm::NetworkBehaviour,
-    tcp, yamux, PeerId, StreamProtocol,
+    autonat, connection_limits, dcutr, identify, identity::Keypair, mdns, multiaddr::Protocol,
+    noise, pnet::PnetConfig, relay, rendezvous, swarm::NetworkBehaviour, tcp, yamux, PeerId,
+    StreamProtocol,
 };
 use std::cmp::PartialEq;
 use std::fs::{read, write};
@@ -16,12 +23,19 @@ use async_compat::Compat;
 use clap::{crate_description, crate_version, Arg, ArgAction, Command};
 use futures::stream::StreamExt;
 use libp2p_stream as stream;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
+use config::Config;
+use limits::StreamLimiter;
+use quota::BandwidthTracker;
+use relaypool::RelayPool;
+
 const PROXY_PROTOCOL: StreamProtocol = StreamProtocol::new("/proxy");
 const PROXY_AGENT: &str = "libp2p-proxy-vpn";
+const RENDEZVOUS_NAMESPACE: &str = "kadugu-sharers";
 
 #[derive(PartialEq)]
 enum Mode {
@@ -36,6 +50,10 @@ struct Behaviour {
     stream: stream::Behaviour,
     relay_client: relay::client::Behaviour,
     dcutr: dcutr::Behaviour,
+    autonat: autonat::Behaviour,
+    connection_limits: connection_limits::Behaviour,
+    mdns: Option<mdns::tokio::Behaviour>,
+    rendezvous_client: rendezvous::client::Behaviour,
 }
 
 #[tokio::main]
@@ -82,12 +100,99 @@ async fn main() -> Result<()> {
                 .action(ArgAction::SetTrue)
                 .help("Expose the internet to other machines in the local network. Use it with -u option"),
         )
+        .arg(
+            Arg::new("quota-mb")
+                .long("quota-mb")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64))
+                .help("Sharer only: cap total traffic per peer to N megabytes per quota window"),
+        )
+        .arg(
+            Arg::new("quota-window")
+                .long("quota-window")
+                .value_name("secs")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("3600")
+                .help("Sharer only: rolling window in seconds over which --quota-mb is enforced"),
+        )
+        .arg(
+            Arg::new("max-connections")
+                .long("max-connections")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .help("Sharer only: cap the total number of established connections"),
+        )
+        .arg(
+            Arg::new("max-connections-per-peer")
+                .long("max-connections-per-peer")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("2")
+                .help("Sharer only: cap simultaneous established connections from a single peer"),
+        )
+        .arg(
+            Arg::new("max-streams-per-peer")
+                .long("max-streams-per-peer")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .help("Sharer only: cap simultaneous open /proxy streams from a single peer"),
+        )
+        .arg(
+            Arg::new("swarm-key")
+                .long("swarm-key")
+                .value_name("path")
+                .help("Path to a pre-shared swarm.key; only peers with the same key can connect"),
+        )
+        .arg(
+            Arg::new("generate-swarm-key")
+                .long("generate-swarm-key")
+                .value_name("path")
+                .help("Generate a new pre-shared swarm key at the given path and exit"),
+        )
+        .arg(
+            Arg::new("discover")
+                .long("discover")
+                .action(ArgAction::SetTrue)
+                .help("Find sharers on the local network via mDNS instead of passing -u"),
+        )
+        .arg(
+            Arg::new("announce")
+                .long("announce")
+                .action(ArgAction::SetTrue)
+                .help("Sharer only: advertise this node on the local network via mDNS"),
+        )
+        .arg(
+            Arg::new("no-mdns")
+                .long("no-mdns")
+                .action(ArgAction::SetTrue)
+                .help("Disable mDNS entirely, for internet-only relay use"),
+        )
+        .arg(
+            Arg::new("find")
+                .long("find")
+                .action(ArgAction::SetTrue)
+                .help("Find a sharer via the rendezvous registry instead of passing -u"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("path")
+                .help("Path to a kadugu.toml with the relay list and proxy endpoints"),
+        )
         .get_matches();
 
+    if let Some(path) = matches.get_one::<String>("generate-swarm-key") {
+        swarmkey::generate(path)?;
+        tracing::info!("Generated swarm key at {}", path);
+        return Ok(());
+    }
+
+    let config = Config::load(matches.get_one::<String>("config").map(String::as_str))?;
+
     let mut accepted_peer_ids = Vec::new();
     let mut mode: Mode = Mode::Undefined;
     let mut sharer_peer_id = PeerId::random();
-    let mut proxy_listen_addr: SocketAddr = SocketAddr::from(([127, 0, 0, 1], 8080));
+    let mut proxy_listen_addr: SocketAddr = config.proxy_listen_addr;
 
     if let Some(peer_ids) = matches.get_many::<String>("sharer") {
         accepted_peer_ids = peer_ids.map(|id| id.to_string()).collect();
@@ -106,10 +211,30 @@ async fn main() -> Result<()> {
         mode = Mode::User;
 
         if matches.get_flag("expose-lan") {
-            proxy_listen_addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+            proxy_listen_addr = SocketAddr::new([0, 0, 0, 0].into(), proxy_listen_addr.port());
+        }
+    } else if matches.get_flag("discover") {
+        tracing::info!("Searching for sharers on the local network via mDNS...");
+        mode = Mode::User;
+
+        if matches.get_flag("expose-lan") {
+            proxy_listen_addr = SocketAddr::new([0, 0, 0, 0].into(), proxy_listen_addr.port());
+        }
+    } else if matches.get_flag("find") {
+        tracing::info!("Searching for sharers in the rendezvous registry...");
+        mode = Mode::User;
+
+        if matches.get_flag("expose-lan") {
+            proxy_listen_addr = SocketAddr::new([0, 0, 0, 0].into(), proxy_listen_addr.port());
         }
     }
 
+    let discover_mode = matches.get_flag("discover");
+    let find_mode = matches.get_flag("find");
+    let announce = matches.get_flag("announce");
+    let enable_mdns = !matches.get_flag("no-mdns")
+        && ((mode == Mode::Sharer && announce) || (mode == Mode::User && discover_mode));
+
     if matches.get_flag("print-peer-id") {
         mode = Mode::PrintPeerId;
     }
@@ -119,40 +244,115 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    // Connection limits apply in sharer mode only; other modes keep libp2p's unlimited defaults.
+    let is_sharer = mode == Mode::Sharer;
+    let max_connections = matches
+        .get_one::<u32>("max-connections")
+        .copied()
+        .filter(|_| is_sharer);
+    let max_connections_per_peer = matches
+        .get_one::<u32>("max-connections-per-peer")
+        .copied()
+        .filter(|_| is_sharer);
+    let max_streams_per_peer = matches.get_one::<usize>("max-streams-per-peer").copied();
+
+    let psk = match matches.get_one::<String>("swarm-key") {
+        Some(path) => {
+            let psk = swarmkey::load(path)?;
+            tracing::info!("Private swarm mode enabled using key from {}", path);
+            Some(psk)
+        }
+        None => None,
+    };
+    // QUIC's handshake has no hook for a pre-shared key, so the only way to keep a
+    // private swarm actually closed to outsiders is to not speak QUIC at all.
+    let is_private_swarm = psk.is_some();
+    if is_private_swarm {
+        tracing::info!("Private swarm mode: disabling QUIC, only the PSK-gated TCP transport is used");
+    }
+
     let key_pair = get_identity().unwrap();
-    let mut swarm = libp2p::SwarmBuilder::with_existing_identity(key_pair)
+    let swarm_builder = libp2p::SwarmBuilder::with_existing_identity(key_pair)
         .with_tokio()
-        .with_tcp(
-            tcp::Config::default(),
-            noise::Config::new,
-            yamux::Config::default,
-        )?
-        .with_quic()
-        .with_dns()?
-        .with_relay_client(noise::Config::new, yamux::Config::default)?
-        .with_behaviour(|key_pair, relay_behaviour| Behaviour {
-            stream: stream::Behaviour::new(),
-            identify: identify::Behaviour::new(
-                identify::Config::new("/proxy/0.0.1".to_string(), key_pair.public())
-                    .with_agent_version(PROXY_AGENT.into()),
-            ),
-            relay_client: relay_behaviour,
-            dcutr: dcutr::Behaviour::new(key_pair.public().to_peer_id()),
-        })?
-        .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(10)))
-        .build();
-
-    let relay_address: Multiaddr =
-        "/ip4/104.131.131.82/udp/4001/quic-v1/p2p/QmaCpDMGvV2BGHeYERUEnRQAwe3N8SzbUtfsmvsqQLuvuJ"
-            .parse()?;
+        .with_other_transport(move |key_pair| {
+            let tcp_transport = tcp::tokio::Transport::new(tcp::Config::default());
+            let tcp_transport = match psk {
+                Some(psk) => tcp_transport
+                    .and_then(move |socket, _| PnetConfig::new(psk).handshake(socket))
+                    .boxed(),
+                None => tcp_transport.boxed(),
+            };
+            Ok(tcp_transport
+                .upgrade(Version::V1Lazy)
+                .authenticate(noise::Config::new(key_pair)?)
+                .multiplex(yamux::Config::default())
+                .boxed())
+        })?;
+
+    let behaviour_fn = |key_pair: &Keypair, relay_behaviour: relay::client::Behaviour| Behaviour {
+        stream: stream::Behaviour::new(),
+        identify: identify::Behaviour::new(
+            identify::Config::new("/proxy/0.0.1".to_string(), key_pair.public())
+                .with_agent_version(PROXY_AGENT.into()),
+        ),
+        relay_client: relay_behaviour,
+        dcutr: dcutr::Behaviour::new(key_pair.public().to_peer_id()),
+        autonat: autonat::Behaviour::new(key_pair.public().to_peer_id(), autonat::Config::default()),
+        connection_limits: connection_limits::Behaviour::new(
+            connection_limits::ConnectionLimits::default()
+                .with_max_established(max_connections)
+                .with_max_established_per_peer(max_connections_per_peer)
+                .with_max_pending_incoming(max_connections)
+                .with_max_pending_outgoing(max_connections),
+        ),
+        mdns: if enable_mdns {
+            Some(
+                mdns::tokio::Behaviour::new(mdns::Config::default(), key_pair.public().to_peer_id())
+                    .unwrap(),
+            )
+        } else {
+            None
+        },
+        rendezvous_client: rendezvous::client::Behaviour::new(key_pair.clone()),
+    };
+
+    let mut swarm = if is_private_swarm {
+        swarm_builder
+            .with_dns()?
+            .with_relay_client(noise::Config::new, yamux::Config::default)?
+            .with_behaviour(behaviour_fn)?
+            .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(10)))
+            .build()
+    } else {
+        swarm_builder
+            .with_quic()
+            .with_dns()?
+            .with_relay_client(noise::Config::new, yamux::Config::default)?
+            .with_behaviour(behaviour_fn)?
+            .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(10)))
+            .build()
+    };
+
+    let mut relay_pool = RelayPool::new(config.relays)?;
+    let rendezvous_namespace = rendezvous::Namespace::new(RENDEZVOUS_NAMESPACE.to_string())?;
 
     if let Mode::PrintPeerId = mode {
         tracing::info!("This machine PeerId: {:?}", swarm.local_peer_id());
         return Ok(());
     }
-    swarm.listen_on("/ip4/0.0.0.0/udp/12007/quic-v1".parse()?)?;
-    swarm.listen_on("/ip6/::/udp/12007/quic-v1".parse()?)?;
-    swarm.dial(relay_address.clone()).unwrap();
+    if !is_private_swarm {
+        swarm.listen_on("/ip4/0.0.0.0/udp/12007/quic-v1".parse()?)?;
+        swarm.listen_on("/ip6/::/udp/12007/quic-v1".parse()?)?;
+    }
+    // Dial every configured relay in parallel; we adopt whichever one responds first
+    // (see RelayPool::adopt) and fail over to the next relay on error. A single
+    // misconfigured relay address shouldn't take down the whole process, so we log and
+    // carry on rather than unwrapping.
+    for relay in relay_pool.all() {
+        if let Err(error) = swarm.dial(relay.clone()) {
+            tracing::warn!("Failed to dial relay {}: {:?}", relay, error);
+        }
+    }
 
     if let Mode::Sharer = mode {
         let incoming_streams = swarm
@@ -162,16 +362,37 @@ async fn main() -> Result<()> {
             .accept(PROXY_PROTOCOL)
             .unwrap();
 
+        let quota_mb = matches.get_one::<u64>("quota-mb").copied();
+        let quota_window = *matches.get_one::<u64>("quota-window").unwrap();
+        if let Some(quota_mb) = quota_mb {
+            tracing::info!(
+                "Enforcing a {} MB per {}s quota per peer",
+                quota_mb,
+                quota_window
+            );
+        }
+        let bandwidth = BandwidthTracker::new(quota_mb, quota_window);
+        bandwidth.clone().spawn_periodic_summary(Duration::from_secs(60));
+        let stream_limiter = StreamLimiter::new(max_streams_per_peer);
+        let upstream_addr = config.upstream_addr;
+
         tokio::spawn(async move {
             // start the proxy server
-            let proxy = proxyserver::HttpProxy::new(SocketAddr::from(([127, 0, 0, 1], 8090)));
+            let proxy = proxyserver::HttpProxy::new(upstream_addr);
 
             if let Err(err) = proxy.run().await {
                 tracing::info!("HTTP proxy error: {:?}", err);
             }
         });
         tokio::spawn(async move {
-            handle_incoming_streams(incoming_streams, accepted_peer_ids).await;
+            handle_incoming_streams(
+                incoming_streams,
+                accepted_peer_ids,
+                bandwidth,
+                stream_limiter,
+                upstream_addr,
+            )
+            .await;
         });
     } else {
         tracing::info!("Searching for sharer peer id...");
@@ -179,19 +400,96 @@ async fn main() -> Result<()> {
 
     let mut sharer_dial_complete = false;
     let mut relay_reservation_complete = false;
+    let mut nat_status = autonat::NatStatus::Unknown;
+    let mut find_query_sent = false;
+    // The relay we're currently registered with, and a timer that fires a bit before our
+    // registration's TTL runs out so we can re-register. `register()` is otherwise only
+    // ever called once, right after the reservation completes, so a long-running sharer
+    // would silently fall out of the rendezvous registry once the TTL elapsed.
+    let mut registered_relay_peer_id: Option<PeerId> = None;
+    // No registration yet, so there's nothing to refresh; replaced with a real interval,
+    // sized off the TTL, once we hear back from the rendezvous point (see `Registered` below).
+    let mut rendezvous_refresh = tokio::time::interval(Duration::from_secs(60 * 60 * 24 * 365));
+    rendezvous_refresh.tick().await;
     // Poll the swarm to make progress.
     loop {
-        let event = swarm.next().await.expect("never terminates");
+        let event = tokio::select! {
+            event = swarm.next() => event.expect("never terminates"),
+            _ = rendezvous_refresh.tick() => {
+                if let (Mode::Sharer, Some(relay_peer_id)) = (&mode, registered_relay_peer_id) {
+                    tracing::info!("Refreshing rendezvous registration before it expires");
+                    if let Err(error) = swarm.behaviour_mut().rendezvous_client.register(
+                        rendezvous_namespace.clone(),
+                        relay_peer_id,
+                        None,
+                    ) {
+                        tracing::warn!("Failed to refresh rendezvous registration: {:?}", error);
+                    }
+                }
+                continue;
+            }
+        };
 
         match event {
             libp2p::swarm::SwarmEvent::ExternalAddrExpired { .. } => {
                 relay_reservation_complete = false;
+                if let Mode::Sharer = mode {
+                    let next_relay = relay_pool.failover().clone();
+                    if let Err(error) = swarm.listen_on(next_relay.with(Protocol::P2pCircuit)) {
+                        tracing::warn!("Failed to listen on failover relay: {:?}", error);
+                    }
+                }
             }
             libp2p::swarm::SwarmEvent::Behaviour(BehaviourEvent::RelayClient(
                 relay::client::Event::ReservationReqAccepted { relay_peer_id, .. },
             )) => {
                 tracing::info!("Reservation with relay {:?} completed ", relay_peer_id);
                 relay_reservation_complete = true;
+                relay_pool.adopt(relay_peer_id);
+
+                if let Mode::Sharer = mode {
+                    if let Err(error) = swarm.behaviour_mut().rendezvous_client.register(
+                        rendezvous_namespace.clone(),
+                        relay_peer_id,
+                        None,
+                    ) {
+                        tracing::warn!("Failed to register with rendezvous point: {:?}", error);
+                    }
+                }
+            }
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviourEvent::Autonat(
+                autonat::Event::StatusChanged { old, new },
+            )) => {
+                tracing::info!("AutoNAT status changed: {:?} -> {:?}", old, new);
+                nat_status = new.clone();
+                match new {
+                    autonat::NatStatus::Public(address) => {
+                        tracing::info!("Node is publicly reachable at {:?}, skipping relay", address);
+                        swarm.add_external_address(address);
+                        if let Mode::Sharer = mode {
+                            // We are directly reachable, no need to hold a relay reservation.
+                            relay_reservation_complete = true;
+                        }
+                    }
+                    autonat::NatStatus::Private => {
+                        if let Mode::Sharer = mode {
+                            relay_reservation_complete = false;
+                            swarm
+                                .listen_on(
+                                    relay_pool.current_address().clone().with(Protocol::P2pCircuit),
+                                )
+                                .unwrap();
+                        }
+                    }
+                    autonat::NatStatus::Unknown => {}
+                }
+            }
+            libp2p::swarm::SwarmEvent::IncomingConnectionError { send_back_addr, error, .. } => {
+                tracing::warn!(
+                    "Rejected incoming connection from {}: {}",
+                    send_back_addr,
+                    error
+                );
             }
             libp2p::swarm::SwarmEvent::OutgoingConnectionError {
                 connection_id: _,
@@ -202,28 +500,116 @@ async fn main() -> Result<()> {
                     //sharer_dial_complete = false;
                     swarm
                         .dial(
-                            relay_address
+                            relay_pool
+                                .current_address()
                                 .clone()
                                 .with(Protocol::P2pCircuit)
                                 .with(Protocol::P2p(sharer_peer_id)),
                         )
                         .unwrap();
+                } else if peer_id.is_some_and(|id| id == relay_pool.current_peer_id()) {
+                    let next_relay = relay_pool.failover().clone();
+                    if let Err(error) = swarm.dial(next_relay) {
+                        tracing::warn!("Failed to dial failover relay: {:?}", error);
+                    }
                 }
             }
             libp2p::swarm::SwarmEvent::Behaviour(BehaviourEvent::Identify(
-                identify::Event::Received { .. },
+                identify::Event::Received { peer_id, info },
             )) => {
+                relay_pool.adopt(peer_id);
                 if let Mode::Sharer = mode {
-                    if relay_reservation_complete == false {
+                    if !matches!(nat_status, autonat::NatStatus::Public(_))
+                        && relay_reservation_complete == false
+                    {
                         swarm
-                            .listen_on(relay_address.clone().with(Protocol::P2pCircuit))
+                            .listen_on(
+                                relay_pool.current_address().clone().with(Protocol::P2pCircuit),
+                            )
                             .unwrap();
                     }
                 } else {
                     if sharer_dial_complete == false {
+                        if find_mode {
+                            if peer_id == relay_pool.current_peer_id() && !find_query_sent {
+                                let relay_peer_id = relay_pool.current_peer_id();
+                                swarm.behaviour_mut().rendezvous_client.discover(
+                                    Some(rendezvous_namespace.clone()),
+                                    None,
+                                    None,
+                                    relay_peer_id,
+                                );
+                                find_query_sent = true;
+                            }
+                        } else if discover_mode && info.agent_version != PROXY_AGENT {
+                            // Not the peer we were hoping to find via mDNS; keep waiting.
+                        } else if discover_mode {
+                            tracing::info!("Found sharer {} via mDNS discovery", peer_id);
+                            sharer_peer_id = peer_id;
+                            tokio::spawn(portforward_connection_handler(
+                                sharer_peer_id,
+                                swarm.behaviour().stream.new_control(),
+                                proxy_listen_addr,
+                            ));
+                            sharer_dial_complete = true;
+                        } else {
+                            if peer_id == sharer_peer_id && !info.listen_addrs.is_empty() {
+                                // Try the sharer's direct address first; we fall back to the
+                                // relay circuit address on OutgoingConnectionError above.
+                                for addr in &info.listen_addrs {
+                                    swarm.add_peer_address(sharer_peer_id, addr.clone());
+                                }
+                                swarm.dial(sharer_peer_id).unwrap();
+                            } else {
+                                swarm
+                                    .dial(
+                                        relay_pool
+                                            .current_address()
+                                            .clone()
+                                            .with(Protocol::P2pCircuit)
+                                            .with(Protocol::P2p(sharer_peer_id)),
+                                    )
+                                    .unwrap();
+                            }
+                            tokio::spawn(portforward_connection_handler(
+                                sharer_peer_id,
+                                swarm.behaviour().stream.new_control(),
+                                proxy_listen_addr,
+                            ));
+                            sharer_dial_complete = true;
+                        }
+                    }
+                }
+            }
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviourEvent::Mdns(mdns::Event::Discovered(
+                peers,
+            ))) => {
+                if let Mode::User = mode {
+                    if discover_mode && !sharer_dial_complete {
+                        for (peer_id, addr) in peers {
+                            tracing::info!("Discovered peer {} at {} via mDNS", peer_id, addr);
+                            swarm.dial(addr).unwrap();
+                        }
+                    }
+                }
+            }
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviourEvent::RendezvousClient(
+                rendezvous::client::Event::Discovered { registrations, .. },
+            )) => {
+                if !sharer_dial_complete {
+                    if let Some(registration) = registrations.first() {
+                        sharer_peer_id = registration.record.peer_id();
+                        tracing::info!(
+                            "Found sharer {} via rendezvous registry",
+                            sharer_peer_id
+                        );
+                        for addr in registration.record.addresses() {
+                            swarm.add_peer_address(sharer_peer_id, addr.clone());
+                        }
                         swarm
                             .dial(
-                                relay_address
+                                relay_pool
+                                    .current_address()
                                     .clone()
                                     .with(Protocol::P2pCircuit)
                                     .with(Protocol::P2p(sharer_peer_id)),
@@ -238,6 +624,20 @@ async fn main() -> Result<()> {
                     }
                 }
             }
+            libp2p::swarm::SwarmEvent::Behaviour(BehaviourEvent::RendezvousClient(
+                rendezvous::client::Event::Registered { rendezvous_node, ttl, .. },
+            )) => {
+                tracing::info!(
+                    "Registered with rendezvous point {} for {}s",
+                    rendezvous_node,
+                    ttl
+                );
+                registered_relay_peer_id = Some(rendezvous_node);
+                // Refresh at half the TTL so a slow re-registration still lands before
+                // the old one expires.
+                rendezvous_refresh = tokio::time::interval(Duration::from_secs(ttl.max(2) / 2));
+                rendezvous_refresh.tick().await;
+            }
             event => tracing::trace!(?event),
             //_ => {}
         }
@@ -296,6 +696,9 @@ async fn portforward_connection_handler(
 async fn handle_incoming_streams(
     mut incoming_streams: stream::IncomingStreams,
     accepted_peer_ids: Vec<String>,
+    bandwidth: BandwidthTracker,
+    stream_limiter: StreamLimiter,
+    upstream_addr: SocketAddr,
 ) -> () {
     while let Some((peer, p2p_stream)) = incoming_streams.next().await {
         let peer_id_str = peer.to_string();
@@ -313,21 +716,41 @@ async fn handle_incoming_streams(
             continue;
         }
 
+        if bandwidth.is_over_quota(&peer).await {
+            tracing::warn!("Peer {} exceeded its bandwidth quota, dropping stream", peer);
+            continue;
+        }
+
+        if !stream_limiter.try_acquire(&peer).await {
+            tracing::warn!("Peer {} exceeded its concurrent stream limit, dropping stream", peer);
+            continue;
+        }
+
+        let bandwidth = bandwidth.clone();
+        let stream_limiter = stream_limiter.clone();
         tokio::spawn(async move {
-            let mut app_stream = TcpStream::connect("127.0.0.1:8090").await.unwrap();
+            let mut app_stream = TcpStream::connect(upstream_addr).await.unwrap();
             let _ = app_stream.set_nodelay(true);
 
             let mut p2p_tokio_stream = Compat::new(p2p_stream);
 
-            let (from_p2p, from_app) =
-                match tokio::io::copy_bidirectional(&mut p2p_tokio_stream, &mut app_stream).await {
-                    Ok((from_p2p, from_app)) => (from_p2p, from_app),
-                    Err(error) => {
-                        tracing::info!("Error copying data from p2p to app stream: {:?}", error);
-                        return;
-                    }
-                };
+            let (from_p2p, from_app) = match copy_bidirectional_metered(
+                &mut p2p_tokio_stream,
+                &mut app_stream,
+                peer,
+                &bandwidth,
+            )
+            .await
+            {
+                Ok((from_p2p, from_app)) => (from_p2p, from_app),
+                Err(error) => {
+                    tracing::info!("Error copying data from p2p to app stream: {:?}", error);
+                    stream_limiter.release(&peer).await;
+                    return;
+                }
+            };
 
+            stream_limiter.release(&peer).await;
             tracing::info!(
                 "P2P stream wrote {} bytes and received {} bytes",
                 from_p2p,
@@ -337,6 +760,64 @@ async fn handle_incoming_streams(
     }
 }
 
+/// Like `tokio::io::copy_bidirectional`, but attributes bytes to `peer` as they're
+/// copied (not only once the stream closes) and bails out as soon as `peer` goes over
+/// its bandwidth quota, so a single long-lived `/proxy` stream can't dodge the cap.
+async fn copy_bidirectional_metered<A, B>(
+    a: &mut A,
+    b: &mut B,
+    peer: PeerId,
+    bandwidth: &BandwidthTracker,
+) -> std::io::Result<(u64, u64)>
+where
+    A: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    B: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (mut a_read, mut a_write) = tokio::io::split(a);
+    let (mut b_read, mut b_write) = tokio::io::split(b);
+
+    let mut a_to_b = [0u8; 8192];
+    let mut b_to_a = [0u8; 8192];
+    let mut a_done = false;
+    let mut b_done = false;
+    let mut from_p2p = 0u64;
+    let mut from_app = 0u64;
+
+    while !(a_done && b_done) {
+        if bandwidth.is_over_quota(&peer).await {
+            tracing::warn!("Peer {} exceeded its bandwidth quota mid-stream, closing", peer);
+            break;
+        }
+
+        tokio::select! {
+            result = a_read.read(&mut a_to_b), if !a_done => {
+                let n = result?;
+                if n == 0 {
+                    a_done = true;
+                    let _ = b_write.shutdown().await;
+                } else {
+                    b_write.write_all(&a_to_b[..n]).await?;
+                    from_p2p += n as u64;
+                    bandwidth.record(peer, n as u64, 0).await;
+                }
+            }
+            result = b_read.read(&mut b_to_a), if !b_done => {
+                let n = result?;
+                if n == 0 {
+                    b_done = true;
+                    let _ = a_write.shutdown().await;
+                } else {
+                    a_write.write_all(&b_to_a[..n]).await?;
+                    from_app += n as u64;
+                    bandwidth.record(peer, 0, n as u64).await;
+                }
+            }
+        }
+    }
+
+    Ok((from_p2p, from_app))
+}
+
 // Create new cert key pair if not found otherwise use existing cert.
 fn get_identity() -> Result<Keypair, Error> {
     // Define the file path where the key pair will be stored
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use libp2p::PeerId;
+use tokio::sync::Mutex;
+
+/// Bytes sent/received by a single peer within the current rolling window.
+#[derive(Default, Clone, Copy)]
+struct PeerUsage {
+    up: u64,
+    down: u64,
+    window_start: Option<Instant>,
+}
+
+/// Tracks per-peer bandwidth usage and enforces an optional quota per rolling window.
+///
+/// Sharers without `--quota-mb` get the old unlimited behaviour; when a quota is set,
+/// peers that exceed it within `window` have new `/proxy` streams refused until the
+/// window resets.
+#[derive(Clone)]
+pub struct BandwidthTracker {
+    usage: Arc<Mutex<HashMap<PeerId, PeerUsage>>>,
+    quota_bytes: Option<u64>,
+    window: Duration,
+}
+
+impl BandwidthTracker {
+    pub fn new(quota_mb: Option<u64>, window_secs: u64) -> Self {
+        Self {
+            usage: Arc::new(Mutex::new(HashMap::new())),
+            quota_bytes: quota_mb.map(|mb| mb * 1024 * 1024),
+            window: Duration::from_secs(window_secs),
+        }
+    }
+
+    /// Returns `true` if `peer` is currently over its quota and new streams should be refused.
+    pub async fn is_over_quota(&self, peer: &PeerId) -> bool {
+        let Some(quota_bytes) = self.quota_bytes else {
+            return false;
+        };
+        let usage = self.usage.lock().await;
+        match usage.get(peer) {
+            Some(entry) => {
+                let in_window = entry
+                    .window_start
+                    .is_some_and(|start| start.elapsed() < self.window);
+                in_window && entry.up + entry.down >= quota_bytes
+            }
+            None => false,
+        }
+    }
+
+    /// Records `up`/`down` bytes transferred with `peer`, resetting the window if it has elapsed.
+    pub async fn record(&self, peer: PeerId, up: u64, down: u64) {
+        let mut usage = self.usage.lock().await;
+        let entry = usage.entry(peer).or_default();
+        let now = Instant::now();
+        let expired = entry
+            .window_start
+            .is_some_and(|start| now.duration_since(start) >= self.window);
+        if entry.window_start.is_none() || expired {
+            entry.window_start = Some(now);
+            entry.up = 0;
+            entry.down = 0;
+        }
+        entry.up += up;
+        entry.down += down;
+    }
+
+    /// Logs a summary line of bytes up/down for every peer with recorded usage.
+    pub async fn log_summary(&self) {
+        let usage = self.usage.lock().await;
+        if usage.is_empty() {
+            return;
+        }
+        for (peer, entry) in usage.iter() {
+            tracing::info!(
+                "Peer {} usage: {} bytes up, {} bytes down",
+                peer,
+                entry.up,
+                entry.down
+            );
+        }
+    }
+
+    /// Spawns a background task that logs the usage summary every `interval`.
+    pub fn spawn_periodic_summary(self, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.log_summary().await;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn under_quota_is_not_over_quota() {
+        let tracker = BandwidthTracker::new(Some(1), 60);
+        let peer = PeerId::random();
+        tracker.record(peer, 100, 100).await;
+        assert!(!tracker.is_over_quota(&peer).await);
+    }
+
+    #[tokio::test]
+    async fn over_quota_is_reported() {
+        let tracker = BandwidthTracker::new(Some(1), 60);
+        let peer = PeerId::random();
+        tracker.record(peer, 1024 * 1024, 0).await;
+        assert!(tracker.is_over_quota(&peer).await);
+    }
+
+    #[tokio::test]
+    async fn window_rollover_resets_usage() {
+        let tracker = BandwidthTracker::new(Some(1), 1);
+        let peer = PeerId::random();
+        tracker.record(peer, 1024 * 1024, 0).await;
+        assert!(tracker.is_over_quota(&peer).await);
+
+        // Once the window has elapsed, the next record() should start a fresh window
+        // instead of accumulating on top of the old (stale) usage.
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        tracker.record(peer, 1, 0).await;
+        assert!(!tracker.is_over_quota(&peer).await);
+    }
+
+    #[tokio::test]
+    async fn no_quota_configured_never_over_quota() {
+        let tracker = BandwidthTracker::new(None, 60);
+        let peer = PeerId::random();
+        tracker.record(peer, u64::MAX / 2, u64::MAX / 2).await;
+        assert!(!tracker.is_over_quota(&peer).await);
+    }
+}
@@ -0,0 +1,122 @@
+use anyhow::{bail, Result};
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+
+/// Tracks a configured list of relays and which one we're currently using, so we can
+/// fail over to the next relay when the current one errors out or its reservation
+/// expires instead of retrying the same dead relay forever.
+pub struct RelayPool {
+    relays: Vec<Multiaddr>,
+    current: usize,
+}
+
+impl RelayPool {
+    /// Builds a pool from `relays`. Every relay must carry a `/p2p/<peer-id>` component —
+    /// `current_peer_id`/`adopt` rely on it being present, so we reject anything missing
+    /// it here rather than panicking the first time it's looked up.
+    pub fn new(relays: Vec<Multiaddr>) -> Result<Self> {
+        assert!(!relays.is_empty(), "at least one relay must be configured");
+        for relay in &relays {
+            if Self::peer_id_of(relay).is_none() {
+                bail!("relay address {} is missing a /p2p/<peer-id> component", relay);
+            }
+        }
+        Ok(Self { relays, current: 0 })
+    }
+
+    pub fn all(&self) -> &[Multiaddr] {
+        &self.relays
+    }
+
+    pub fn current_address(&self) -> &Multiaddr {
+        &self.relays[self.current]
+    }
+
+    pub fn current_peer_id(&self) -> PeerId {
+        Self::peer_id_of(self.current_address())
+            .expect("relay address must include a /p2p peer id")
+    }
+
+    /// Rotates to the next configured relay, wrapping around.
+    pub fn failover(&mut self) -> &Multiaddr {
+        self.current = (self.current + 1) % self.relays.len();
+        tracing::info!("Failing over to relay {}", self.current_address());
+        self.current_address()
+    }
+
+    /// Adopts `relay_peer_id` as the current relay if it matches one of the configured
+    /// relays. We dial every configured relay at startup in parallel, so whichever one
+    /// actually answers first (reservation accepted, or identified) should become
+    /// "current" instead of always sticking with relay 0 until it errors out — a relay
+    /// that merely stalls without erroring would otherwise block failover forever.
+    pub fn adopt(&mut self, relay_peer_id: PeerId) {
+        let Some(index) = self
+            .relays
+            .iter()
+            .position(|addr| Self::peer_id_of(addr) == Some(relay_peer_id))
+        else {
+            return;
+        };
+        if index != self.current {
+            tracing::info!(
+                "Adopting relay {} as current (first to respond)",
+                self.relays[index]
+            );
+            self.current = index;
+        }
+    }
+
+    fn peer_id_of(addr: &Multiaddr) -> Option<PeerId> {
+        addr.iter().find_map(|p| match p {
+            Protocol::P2p(peer_id) => Some(peer_id),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relay(peer: PeerId) -> Multiaddr {
+        format!("/ip4/127.0.0.1/tcp/4001/p2p/{}", peer)
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn new_rejects_relay_without_peer_id() {
+        let bad: Multiaddr = "/ip4/127.0.0.1/tcp/4001".parse().unwrap();
+        assert!(RelayPool::new(vec![bad]).is_err());
+    }
+
+    #[test]
+    fn failover_wraps_around() {
+        let a = PeerId::random();
+        let b = PeerId::random();
+        let mut pool = RelayPool::new(vec![relay(a), relay(b)]).unwrap();
+        assert_eq!(pool.current_peer_id(), a);
+        pool.failover();
+        assert_eq!(pool.current_peer_id(), b);
+        pool.failover();
+        assert_eq!(pool.current_peer_id(), a);
+    }
+
+    #[test]
+    fn adopt_switches_to_matching_relay() {
+        let a = PeerId::random();
+        let b = PeerId::random();
+        let mut pool = RelayPool::new(vec![relay(a), relay(b)]).unwrap();
+        assert_eq!(pool.current_peer_id(), a);
+        pool.adopt(b);
+        assert_eq!(pool.current_peer_id(), b);
+    }
+
+    #[test]
+    fn adopt_ignores_unknown_peer() {
+        let a = PeerId::random();
+        let mut pool = RelayPool::new(vec![relay(a)]).unwrap();
+        pool.adopt(PeerId::random());
+        assert_eq!(pool.current_peer_id(), a);
+    }
+}
@@ -0,0 +1,111 @@
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use libp2p::pnet::PreSharedKey;
+use rand::RngCore;
+
+const PSK_HEADER: &str = "/key/swarm/psk/1.0.0/";
+const PSK_CODEC: &str = "/base16/";
+
+/// Loads a pre-shared network key from `path`, in the IPFS `swarm.key` format:
+/// a header line, a codec line, and a 32-byte key encoded as hex.
+///
+/// Note: the PSK handshake only exists for the TCP transport (via `pnet`). QUIC has no
+/// equivalent hook, so `main` disables QUIC entirely whenever a swarm key is loaded —
+/// otherwise an outsider could dial in over QUIC and skip the pre-shared key check.
+pub fn load(path: &str) -> Result<PreSharedKey> {
+    let contents = read_to_string(path)
+        .with_context(|| format!("failed to read swarm key from {}", path))?;
+    let mut lines = contents.lines();
+
+    let header = lines.next().unwrap_or_default().trim();
+    if header != PSK_HEADER.trim_end_matches('/') && header != PSK_HEADER {
+        bail!("unexpected swarm key header: {:?}", header);
+    }
+    let codec = lines.next().unwrap_or_default().trim();
+    if codec != PSK_CODEC.trim_end_matches('/') && codec != PSK_CODEC {
+        bail!("unexpected swarm key codec: {:?}", codec);
+    }
+    let hex_key = lines.next().unwrap_or_default().trim();
+    let bytes = hex::decode(hex_key).context("swarm key is not valid hex")?;
+    if bytes.len() != 32 {
+        bail!("swarm key must be 32 bytes, got {}", bytes.len());
+    }
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&bytes);
+    Ok(PreSharedKey::new(key))
+}
+
+/// Generates a fresh pre-shared key and writes it to `path` in the IPFS `swarm.key` format.
+pub fn generate(path: &str) -> Result<()> {
+    if Path::new(path).exists() {
+        bail!("{} already exists, refusing to overwrite", path);
+    }
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    let contents = format!("{}\n{}\n{}\n", PSK_HEADER, PSK_CODEC, hex::encode(key));
+    write(path, contents).with_context(|| format!("failed to write swarm key to {}", path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("kadugu-swarmkey-test-{}-{}", std::process::id(), name))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn generate_then_load_round_trips() {
+        let path = temp_path("roundtrip");
+        generate(&path).unwrap();
+        assert!(load(&path).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn generate_refuses_to_overwrite() {
+        let path = temp_path("overwrite");
+        generate(&path).unwrap();
+        assert!(generate(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_wrong_header() {
+        let path = temp_path("bad-header");
+        write(&path, format!("/not/the/right/header/\n{}\n{}\n", PSK_CODEC, hex::encode([0u8; 32]))).unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_wrong_codec() {
+        let path = temp_path("bad-codec");
+        write(&path, format!("{}\n/not/base16/\n{}\n", PSK_HEADER, hex::encode([0u8; 32]))).unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_wrong_length() {
+        let path = temp_path("bad-length");
+        write(&path, format!("{}\n{}\n{}\n", PSK_HEADER, PSK_CODEC, hex::encode([0u8; 16]))).unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_rejects_non_hex() {
+        let path = temp_path("bad-hex");
+        write(&path, format!("{}\n{}\nnot hex\n", PSK_HEADER, PSK_CODEC)).unwrap();
+        assert!(load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}